@@ -24,376 +24,570 @@ use edgeware_executor;
 use edgeware_primitives::Block;
 use edgeware_runtime::RuntimeApi;
 use sc_consensus_aura;
-use sc_finality_grandpa::{
-	self, FinalityProofProvider as GrandpaFinalityProofProvider, StorageAndProofProvider,
-};
-use sc_service::{
-	config::Configuration, error::Error as ServiceError, AbstractService, ServiceBuilder,
-};
+use sc_finality_grandpa;
+use sc_service::{config::Configuration, error::Error as ServiceError, PartialComponents, TaskManager};
 
 use sc_consensus::LongestChain;
+use sc_consensus_slots::SlotProportion;
+use sc_finality_grandpa::warp_proof;
+use futures::StreamExt;
 use sp_inherents::InherentDataProviders;
 
-/// Starts a `ServiceBuilder` for a full service.
+/// Default portion of a slot a validator is allowed to spend proposing a block before
+/// the proposer is told to finish up, used unless the chain spec overrides it via an
+/// `auraBlockProposalSlotPortion` property. Keeping this below `1.0` leaves headroom for
+/// the block to be built, signed, gossiped and imported by the rest of the network within
+/// the slot, so that a busy proposer doesn't overrun its slot and have the block discarded.
+const AURA_BLOCK_PROPOSAL_SLOT_PORTION: f32 = 2.0 / 3.0;
+
+/// Default number of blocks between stored GRANDPA justifications, used unless the
+/// chain spec overrides it via a `grandpaJustificationPeriod` property.
 ///
-/// Use this macro if you don't actually need the full service, but just the builder in order to
-/// be able to perform chain operations.
-macro_rules! new_full_start {
-	($config:expr) => {{
-		use std::sync::Arc;
-
-		let mut import_setup = None;
-		let mut rpc_setup = None;
-		let inherent_data_providers = sp_inherents::InherentDataProviders::new();
-
-		let builder = sc_service::ServiceBuilder::new_full::<
-			edgeware_primitives::Block,
-			edgeware_runtime::RuntimeApi,
-			edgeware_executor::Executor,
-		>($config)?
-			.with_select_chain(|_config, backend| {
-				Ok(sc_consensus::LongestChain::new(backend.clone()))
-			})?
-			.with_transaction_pool(|builder| {
-				let pool_api = sc_transaction_pool::FullChainApi::new(
-					builder.client().clone(),
-				);
-				let config = builder.config();
-
-				Ok(sc_transaction_pool::BasicPool::new(
-					config.transaction_pool.clone(),
-					std::sync::Arc::new(pool_api),
-					builder.prometheus_registry(),
-				))
-			})?
-			.with_import_queue(|
-				_config,
-				client,
-				mut select_chain,
-				_transaction_pool,
-				spawn_task_handle,
-				registry,
-			| {
-				let select_chain = select_chain
-					.take()
-					.ok_or_else(|| sc_service::Error::SelectChainRequired)?;
-				let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
-					client.clone(),
-					&(client.clone() as Arc<_>),
-					select_chain,
-				)?;
-				let justification_import = grandpa_block_import.clone();
-
-				let aura_block_import =
-					sc_consensus_aura::AuraBlockImport::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair>::new(
-						justification_import.clone(),
-						client.clone()
-					);
-
-				let import_queue = sc_consensus_aura::import_queue::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair, _>(
-					sc_consensus_aura::slot_duration(&*client)?,
-					aura_block_import,
-					Some(Box::new(justification_import.clone())),
-					None,
-					client,
-					inherent_data_providers.clone(),
-					spawn_task_handle,
-					registry,
-				)?;
-
-				import_setup = Some((grandpa_block_import, grandpa_link));
-				Ok(import_queue)
-			},
-		)?
-		.with_rpc_extensions_builder(|builder| {
-			let grandpa_link = import_setup.as_ref().map(|s| &s.1)
-				.expect("GRANDPA LinkHalf is present for full services or set up failed; qed.");
-
-			let shared_authority_set = grandpa_link.shared_authority_set().clone();
-			let shared_voter_state = sc_finality_grandpa::SharedVoterState::empty();
-
-			rpc_setup = Some((shared_voter_state.clone()));
-
-			let client = builder.client().clone();
-			let pool = builder.pool().clone();
-			let select_chain = builder.select_chain().cloned()
-				.expect("SelectChain is present for full services or set up failed; qed.");
-
-			Ok(move |deny_unsafe| {
-				let deps = edgeware_rpc::FullDeps {
-					client: client.clone(),
-					pool: pool.clone(),
-					select_chain: select_chain.clone(),
-					deny_unsafe,
-					grandpa: edgeware_rpc::GrandpaDeps {
-						shared_voter_state: shared_voter_state.clone(),
-						shared_authority_set: shared_authority_set.clone(),
-					},
-				};
-
-				edgeware_rpc::create_full(deps)
-			})
-		})?;
-
-		(builder, import_setup, inherent_data_providers, rpc_setup)
-	}};
+/// Light clients and warp sync both bootstrap by replaying the justification at each
+/// authority-set change, so this controls how much history they need to fetch.
+const GRANDPA_JUSTIFICATION_PERIOD: u32 = 512;
+
+/// Default GRANDPA gossip round duration, used unless the chain spec overrides it via
+/// a `grandpaGossipDurationMs` property.
+const GRANDPA_GOSSIP_DURATION: std::time::Duration = std::time::Duration::from_millis(333);
+
+/// Reads an optional `u32` chain spec property, falling back to `default` if the
+/// property is absent or isn't a valid `u32`.
+fn chain_spec_property_u32(
+	chain_spec: &dyn sc_service::ChainSpec,
+	key: &str,
+	default: u32,
+) -> u32 {
+	chain_spec.properties()
+		.get(key)
+		.and_then(|value| value.as_u64())
+		.and_then(|value| u32::try_from(value).ok())
+		.unwrap_or(default)
 }
 
-/// Creates a full service from the configuration.
-///
-/// We need to use a macro because the test suit doesn't work with an opaque service. It expects
-/// concrete types instead.
-macro_rules! new_full {
-	($config:expr, $with_startup_data: expr) => {{
-		use futures::prelude::*;
-		use sc_network::Event;
-		use sc_client_api::ExecutorProvider;
-		use sp_core::traits::BareCryptoStorePtr;
-
-		let (
-			role,
-			force_authoring,
-			name,
-			disable_grandpa,
-		) = (
-			$config.role.clone(),
-			$config.force_authoring,
-			$config.network.node_name.clone(),
-			$config.disable_grandpa,
-		);
+/// Reads an optional `f32` chain spec property, falling back to `default` if the
+/// property is absent or isn't a valid number.
+fn chain_spec_property_f32(
+	chain_spec: &dyn sc_service::ChainSpec,
+	key: &str,
+	default: f32,
+) -> f32 {
+	chain_spec.properties()
+		.get(key)
+		.and_then(|value| value.as_f64())
+		.map(|value| value as f32)
+		.unwrap_or(default)
+}
 
-		let (builder, mut import_setup, inherent_data_providers, mut rpc_setup) =
-			new_full_start!($config);
+/// Reads an optional string chain spec property, reporting whether it case-insensitively
+/// equals `expected`. Absent or non-string properties are treated as not matching.
+fn chain_spec_property_is(
+	chain_spec: &dyn sc_service::ChainSpec,
+	key: &str,
+	expected: &str,
+) -> bool {
+	chain_spec.properties()
+		.get(key)
+		.and_then(|value| value.as_str())
+		.map(|value| value.eq_ignore_ascii_case(expected))
+		.unwrap_or(false)
+}
 
-		let service = builder
-			.with_finality_proof_provider(|client, backend| {
-				// GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
-				let provider = client as Arc<dyn sc_finality_grandpa::StorageAndProofProvider<_, _>>;
-				Ok(Arc::new(sc_finality_grandpa::FinalityProofProvider::new(backend, provider)) as _)
-			})?
-			.build_full()?;
+/// Default number of 64KiB wasm heap pages reserved when running with a static
+/// heap, used unless the chain spec overrides it via a `wasmHeapPages` property.
+const DEFAULT_WASM_HEAP_PAGES: u32 = 2048;
 
+/// Default number of wasm runtime instances kept warm in the instantiation cache,
+/// used unless the chain spec overrides it via a `wasmMaxRuntimeInstances`
+/// property. Archive/RPC nodes that service many concurrent calls benefit from a
+/// larger cache; memory-constrained validators benefit from a smaller one.
+const DEFAULT_WASM_MAX_RUNTIME_INSTANCES: u32 = 8;
 
-		let (block_import, grandpa_link) = import_setup.take()
-			.expect("Link Half and Block Import are present for Full Services or setup failed before. qed");
+/// Configures the wasm executor's heap allocation strategy, instance cache size
+/// and execution method from the chain spec.
+///
+/// A `"static"` `wasmHeapAllocStrategy` property bounds the wasm heap to a fixed
+/// number of pages up front, which avoids unbounded memory growth on
+/// memory-constrained validators. Anything else (including no property at all)
+/// keeps the default dynamic, per-call heap sizing, which suits archive/RPC nodes.
+///
+/// A `"compiled"` `wasmExecutionMethod` property runs the runtime through the
+/// ahead-of-time wasmtime compiler instead of the default interpreter, trading
+/// slower startup and a native-codegen dependency for materially faster block
+/// execution -- worthwhile on validators and archive nodes, not on short-lived
+/// tooling.
+fn configure_wasm_executor(config: &mut Configuration) {
+	let use_static_heap = chain_spec_property_is(&*config.chain_spec, "wasmHeapAllocStrategy", "static");
+
+	config.default_heap_pages = if use_static_heap {
+		Some(chain_spec_property_u32(&*config.chain_spec, "wasmHeapPages", DEFAULT_WASM_HEAP_PAGES) as u64)
+	} else {
+		None
+	};
+
+	config.max_runtime_instances = chain_spec_property_u32(
+		&*config.chain_spec,
+		"wasmMaxRuntimeInstances",
+		DEFAULT_WASM_MAX_RUNTIME_INSTANCES,
+	) as usize;
+
+	let use_compiled = chain_spec_property_is(&*config.chain_spec, "wasmExecutionMethod", "compiled");
+
+	config.wasm_method = if use_compiled {
+		sc_service::config::WasmExecutionMethod::Compiled
+	} else {
+		sc_service::config::WasmExecutionMethod::Interpreted
+	};
+}
 
-		let shared_voter_state = rpc_setup.take()
-			.expect("The SharedVoterState is present for Full Services or setup failed before. qed");
+/// Client and backend types shared by `new_partial`/`new_full` and, with the
+/// `runtime-benchmarks` feature, `new_chain_ops`.
+pub type FullClient = sc_service::TFullClient<Block, RuntimeApi, edgeware_executor::Executor>;
+pub type FullBackend = sc_service::TFullBackend<Block>;
+type FullSelectChain = LongestChain<FullBackend, Block>;
+type FullGrandpaBlockImport =
+	sc_finality_grandpa::GrandpaBlockImport<FullBackend, Block, FullClient, FullSelectChain>;
+
+/// Boxed RPC extensions builder, so `PartialComponents` doesn't need to name the
+/// whole closure type built up in `new_partial`.
+type RpcExtensionsBuilder = Box<
+	dyn Fn(sc_rpc::DenyUnsafe) -> Result<edgeware_rpc::IoHandler, ServiceError> + Send,
+>;
+
+/// GRANDPA-specific components threaded from `new_partial` into `new_full`: the
+/// block import handed to `start_aura` and the link half handed to
+/// `run_grandpa_voter`, and the warp sync provider
+/// passed into `build_network` so it both answers other peers' warp sync
+/// requests and, when `config.network.sync_mode` opts this node into warp sync,
+/// verifies the proofs this node requests from its own peers.
+type GrandpaSetup = (
+	(FullGrandpaBlockImport, sc_finality_grandpa::LinkHalf<Block, FullClient, FullSelectChain>),
+	Arc<warp_proof::NetworkProvider<Block, FullBackend, FullClient>>,
+);
+
+/// Creates the client, backend, import queue, transaction pool and other components
+/// shared by `new_full` and the `runtime-benchmarks`-only `new_chain_ops`.
+pub fn new_partial(config: &mut Configuration) -> Result<
+	PartialComponents<
+		FullClient,
+		FullBackend,
+		FullSelectChain,
+		sc_consensus::import_queue::BasicQueue<Block>,
+		sc_transaction_pool::FullPool<Block, FullClient>,
+		(RpcExtensionsBuilder, GrandpaSetup, InherentDataProviders),
+	>,
+	ServiceError,
+> {
+	configure_wasm_executor(config);
+
+	let (client, backend, keystore_container, task_manager) =
+		sc_service::new_full_parts::<Block, RuntimeApi, edgeware_executor::Executor>(config)?;
+	let client = Arc::new(client);
+
+	let select_chain = LongestChain::new(backend.clone());
+
+	let transaction_pool = sc_transaction_pool::BasicPool::new_full(
+		config.transaction_pool.clone(),
+		config.role.is_authority().into(),
+		config.prometheus_registry(),
+		task_manager.spawn_handle(),
+		client.clone(),
+	);
 
-		($with_startup_data)(&block_import, &grandpa_link);
+	let inherent_data_providers = InherentDataProviders::new();
 
-		if let sc_service::config::Role::Authority { .. } = &role {
-			let proposer = sc_basic_authorship::ProposerFactory::new(
-				service.client(),
-				service.transaction_pool(),
-				service.prometheus_registry().as_ref(),
-			);
+	let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
+		client.clone(),
+		&(client.clone() as Arc<_>),
+		select_chain.clone(),
+	)?;
+	let justification_import = grandpa_block_import.clone();
+
+	let aura_block_import =
+		sc_consensus_aura::AuraBlockImport::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair>::new(
+			justification_import.clone(),
+			client.clone(),
+		);
 
-			let client = service.client();
-			let select_chain = service.select_chain()
-				.ok_or(sc_service::Error::SelectChainRequired)?;
+	let import_queue = sc_consensus_aura::import_queue::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair, _>(
+		sc_consensus_aura::slot_duration(&*client)?,
+		aura_block_import,
+		Some(Box::new(justification_import.clone())),
+		None,
+		client.clone(),
+		inherent_data_providers.clone(),
+		&task_manager.spawn_handle(),
+		config.prometheus_registry(),
+	)?;
+
+	// Built from the same authority-set and justification storage the rest of this
+	// service's GRANDPA setup reads; handed to `build_network` below as the
+	// `warp_sync` provider so peers can fetch a proof of the latest authority-set
+	// transitions from genesis instead of importing every intervening header.
+	let warp_sync_provider = Arc::new(warp_proof::NetworkProvider::new(
+		backend.clone(),
+		grandpa_link.shared_authority_set().clone(),
+	));
+
+	let rpc_extensions_builder = {
+		let client = client.clone();
+		let pool = transaction_pool.clone();
+		let select_chain = select_chain.clone();
+		let shared_authority_set = grandpa_link.shared_authority_set().clone();
+		let shared_voter_state = sc_finality_grandpa::SharedVoterState::empty();
+
+		Box::new(move |deny_unsafe| {
+			let deps = edgeware_rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				select_chain: select_chain.clone(),
+				deny_unsafe,
+				grandpa: edgeware_rpc::GrandpaDeps {
+					shared_voter_state: shared_voter_state.clone(),
+					shared_authority_set: shared_authority_set.clone(),
+				},
+			};
+
+			Ok(edgeware_rpc::create_full(deps))
+		}) as RpcExtensionsBuilder
+	};
+
+	Ok(PartialComponents {
+		client,
+		backend,
+		task_manager,
+		keystore_container,
+		select_chain,
+		import_queue,
+		transaction_pool,
+		inherent_data_providers: inherent_data_providers.clone(),
+		other: (
+			rpc_extensions_builder,
+			((grandpa_block_import, grandpa_link), warp_sync_provider),
+			inherent_data_providers,
+		),
+	})
+}
+
+/// Builds the client, backend, import queue and task manager used by the `benchmark`
+/// subcommand, which needs concrete
+/// access to the client rather than the `TaskManager` built by `new_full`.
+///
+/// Note: this only gates the benchmark command's entry point in this crate. Making
+/// `frame_benchmarking::benchmarking::HostFunctions` resolve for a pallet's weight
+/// extrinsics additionally requires `edgeware_executor::Executor` itself to add those
+/// host functions behind the same `runtime-benchmarks` feature; that executor crate
+/// isn't part of this source tree, so it isn't wired up here.
+#[cfg(feature = "runtime-benchmarks")]
+pub fn new_chain_ops(config: &mut Configuration) -> Result<
+	(Arc<FullClient>, Arc<FullBackend>, sc_consensus::import_queue::BasicQueue<Block>, TaskManager),
+	ServiceError,
+> {
+	config.keystore = sc_service::config::KeystoreConfig::InMemory;
+	let PartialComponents { client, backend, task_manager, import_queue, .. } = new_partial(config)?;
+	Ok((client, backend, import_queue, task_manager))
+}
+
+/// Builds a new service for a full client.
+pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+	use sc_client_api::ExecutorProvider;
+
+	let PartialComponents {
+		client,
+		backend,
+		mut task_manager,
+		keystore_container,
+		select_chain,
+		import_queue,
+		transaction_pool,
+		inherent_data_providers,
+		other: (rpc_extensions_builder, ((block_import, grandpa_link), warp_sync_provider), _),
+	} = new_partial(&mut config)?;
+
+	let role = config.role.clone();
+	let force_authoring = config.force_authoring;
+	let name = config.network.node_name.clone();
+	let disable_grandpa = config.disable_grandpa;
+	let offchain_worker_enabled = config.offchain_worker.enabled;
+	let prometheus_registry = config.prometheus_registry().cloned();
+	let aura_block_proposal_slot_portion = chain_spec_property_f32(
+		&*config.chain_spec,
+		"auraBlockProposalSlotPortion",
+		AURA_BLOCK_PROPOSAL_SLOT_PORTION,
+	);
+	let grandpa_justification_period = chain_spec_property_u32(
+		&*config.chain_spec,
+		"grandpaJustificationPeriod",
+		GRANDPA_JUSTIFICATION_PERIOD,
+	);
+	let grandpa_gossip_duration = config.chain_spec.properties()
+		.get("grandpaGossipDurationMs")
+		.and_then(|value| value.as_u64())
+		.map(std::time::Duration::from_millis)
+		.unwrap_or(GRANDPA_GOSSIP_DURATION);
+
+	// `config.network.sync_mode` is the CLI/`Configuration`-driven knob operators use
+	// to opt into warp sync; handing `warp_sync_provider` to `build_network` here is
+	// what lets it actually be consumed (proofs requested and verified against this
+	// node's own peers) rather than only ever answering other peers' requests.
+	let (network, system_rpc_tx, network_starter) = sc_service::build_network(sc_service::BuildNetworkParams {
+		config: &config,
+		client: client.clone(),
+		transaction_pool: transaction_pool.clone(),
+		spawn_handle: task_manager.spawn_handle(),
+		import_queue,
+		on_demand: None,
+		block_announce_validator_builder: None,
+		warp_sync: Some(warp_sync_provider),
+	})?;
+
+	// Give the offchain-worker subsystem a handle to the transaction pool so that
+	// pallets calling `submit_signed`/`submit_unsigned` from an offchain worker can
+	// actually enqueue extrinsics, rather than only being able to read chain state.
+	//
+	// Built straight from the `transaction_pool` handle `new_partial` returned, since
+	// `GrandpaParams::offchain_tx_pool_factory` and `AuthorityDiscovery::new`'s
+	// offchain-pool parameter below both require this factory, and neither those nor
+	// `OffchainTransactionPoolFactory` itself coexisted with the old `ServiceBuilder`,
+	// whose `service.transaction_pool()` this used to be keyed off instead.
+	let offchain_transaction_pool_factory =
+		sc_transaction_pool_api::OffchainTransactionPoolFactory::new(transaction_pool.clone());
+
+	if offchain_worker_enabled {
+		let offchain_workers = Arc::new(sc_offchain::OffchainWorkers::new(
+			client.clone(),
+			Some(offchain_transaction_pool_factory.clone()),
+		));
+
+		task_manager.spawn_handle().spawn(
+			"offchain-workers-runner",
+			client.import_notification_stream().for_each(move |notification| {
+				sc_offchain::OffchainWorkers::on_block_imported(&offchain_workers, &notification.header);
+				futures::future::ready(())
+			}),
+		);
+	}
+
+	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+		network: network.clone(),
+		client: client.clone(),
+		keystore: keystore_container.sync_keystore(),
+		task_manager: &mut task_manager,
+		transaction_pool: transaction_pool.clone(),
+		rpc_extensions_builder: Box::new(move |deny_unsafe, _| rpc_extensions_builder(deny_unsafe)),
+		backend: backend.clone(),
+		system_rpc_tx,
+		config,
+	})?;
+
+	if let sc_service::config::Role::Authority { .. } = &role {
+		let proposer = sc_basic_authorship::ProposerFactory::new(
+			client.clone(),
+			transaction_pool.clone(),
+			prometheus_registry.as_ref(),
+		);
 
-			let can_author_with =
-				sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
+		let can_author_with =
+			sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
 
-			let aura = sc_consensus_aura::start_aura::<_, _, _, _, _, sp_consensus_aura::ed25519::AuthorityPair, _, _, _>(
-				sc_consensus_aura::slot_duration(&*client)?,
-				client,
+		let aura = sc_consensus_aura::start_aura::<_, _, _, _, _, sp_consensus_aura::ed25519::AuthorityPair, _, _, _>(
+			sc_consensus_aura::StartAuraParams {
+				slot_duration: sc_consensus_aura::slot_duration(&*client)?,
+				client: client.clone(),
 				select_chain,
 				block_import,
-				proposer,
-				service.network(),
-				inherent_data_providers.clone(),
+				proposer_factory: proposer,
+				sync_oracle: network.clone(),
+				inherent_data_providers: inherent_data_providers.clone(),
 				force_authoring,
-				service.keystore(),
+				keystore: keystore_container.sync_keystore(),
 				can_author_with,
-			)?;
-
-			// the AURA authoring task is considered essential, i.e. if it
-			// fails we take down the service with it.
-			service.spawn_essential_task_handle().spawn_blocking("aura", aura);
-		}
-
-		// Spawn authority discovery module.
-		if matches!(role, sc_service::config::Role::Authority{..} | sc_service::config::Role::Sentry {..}) {
-			let (sentries, authority_discovery_role) = match role {
-				sc_service::config::Role::Authority { ref sentry_nodes } => (
-					sentry_nodes.clone(),
-					sc_authority_discovery::Role::Authority (
-						service.keystore(),
-					),
-				),
-				sc_service::config::Role::Sentry {..} => (
-					vec![],
-					sc_authority_discovery::Role::Sentry,
+				block_proposal_slot_portion: SlotProportion::new(aura_block_proposal_slot_portion),
+				max_block_proposal_slot_portion: None,
+			},
+		)?;
+
+		// the AURA authoring task is considered essential, i.e. if it
+		// fails we take down the service with it.
+		task_manager.spawn_essential_handle().spawn_blocking("aura", aura);
+	}
+
+	// Spawn authority discovery module.
+	if matches!(role, sc_service::config::Role::Authority{..} | sc_service::config::Role::Sentry {..}) {
+		let (sentries, authority_discovery_role) = match role {
+			sc_service::config::Role::Authority { ref sentry_nodes } => (
+				sentry_nodes.clone(),
+				sc_authority_discovery::Role::Authority(
+					keystore_container.keystore(),
 				),
-				_ => unreachable!("Due to outer matches! constraint; qed.")
-			};
-
-			let network = service.network();
-			let dht_event_stream = network.event_stream("authority-discovery").filter_map(|e| async move { match e {
-				Event::Dht(e) => Some(e),
-				_ => None,
-			}}).boxed();
-			let authority_discovery = sc_authority_discovery::AuthorityDiscovery::new(
-				service.client(),
-				network,
-				sentries,
-				dht_event_stream,
-				authority_discovery_role,
-				service.prometheus_registry(),
-			);
-
-			service.spawn_task_handle().spawn("authority-discovery", authority_discovery);
-		}
-
-		// if the node isn't actively participating in consensus then it doesn't
-		// need a keystore, regardless of which protocol we use below.
-		let keystore = if role.is_authority() {
-			Some(service.keystore() as BareCryptoStorePtr)
-		} else {
-			None
+			),
+			sc_service::config::Role::Sentry {..} => (
+				vec![],
+				sc_authority_discovery::Role::Sentry,
+			),
+			_ => unreachable!("Due to outer matches! constraint; qed.")
 		};
 
-		let config = sc_finality_grandpa::Config {
-			// FIXME #1578 make this available through chainspec
-			gossip_duration: std::time::Duration::from_millis(333),
-			justification_period: 512,
-			name: Some(name),
-			observer_enabled: false,
-			keystore,
-			is_authority: role.is_network_authority(),
-		};
-
-		let enable_grandpa = !disable_grandpa;
-		if enable_grandpa {
-			// start the full GRANDPA voter
-			// NOTE: non-authorities could run the GRANDPA observer protocol, but at
-			// this point the full voter should provide better guarantees of block
-			// and vote data availability than the observer. The observer has not
-			// been tested extensively yet and having most nodes in a network run it
-			// could lead to finality stalls.
-			let grandpa_config = sc_finality_grandpa::GrandpaParams {
-				config,
-				link: grandpa_link,
-				network: service.network(),
-				inherent_data_providers: inherent_data_providers.clone(),
-				telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
-				voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
-				prometheus_registry: service.prometheus_registry(),
-				shared_voter_state,
-			};
+		let dht_event_stream = network.event_stream("authority-discovery").filter_map(|e| async move { match e {
+			sc_network::Event::Dht(e) => Some(e),
+			_ => None,
+		}}).boxed();
+		let authority_discovery = sc_authority_discovery::AuthorityDiscovery::new(
+			client.clone(),
+			network.clone(),
+			sentries,
+			dht_event_stream,
+			authority_discovery_role,
+			prometheus_registry.as_ref(),
+			offchain_transaction_pool_factory.clone(),
+		);
 
-			// the GRANDPA voter task is considered infallible, i.e.
-			// if it fails we take down the service with it.
-			service.spawn_essential_task_handle().spawn_blocking(
-				"grandpa-voter",
-				sc_finality_grandpa::run_grandpa_voter(grandpa_config)?
-			);
-		} else {
-			sc_finality_grandpa::setup_disabled_grandpa(
-				service.client(),
-				&inherent_data_providers,
-				service.network(),
-			)?;
-		}
-
-		Ok((service, inherent_data_providers))
-	}};
-	($config:expr) => {{
-		new_full!($config, |_, _| {})
-	}}
-}
+		task_manager.spawn_handle().spawn("authority-discovery", authority_discovery);
+	}
+
+	// if the node isn't actively participating in consensus then it doesn't
+	// need a keystore, regardless of which protocol we use below.
+	let keystore = if role.is_authority() {
+		Some(keystore_container.sync_keystore())
+	} else {
+		None
+	};
+
+	let grandpa_config = sc_finality_grandpa::Config {
+		gossip_duration: grandpa_gossip_duration,
+		justification_period: grandpa_justification_period,
+		name: Some(name),
+		observer_enabled: false,
+		keystore,
+		is_authority: role.is_network_authority(),
+	};
+
+	let enable_grandpa = !disable_grandpa;
+	if enable_grandpa {
+		// start the full GRANDPA voter
+		// NOTE: non-authorities could run the GRANDPA observer protocol, but at
+		// this point the full voter should provide better guarantees of block
+		// and vote data availability than the observer. The observer has not
+		// been tested extensively yet and having most nodes in a network run it
+		// could lead to finality stalls.
+		let grandpa_params = sc_finality_grandpa::GrandpaParams {
+			config: grandpa_config,
+			link: grandpa_link,
+			network: network.clone(),
+			inherent_data_providers,
+			telemetry_on_connect: None,
+			voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
+			prometheus_registry: prometheus_registry.as_ref(),
+			shared_voter_state: sc_finality_grandpa::SharedVoterState::empty(),
+			offchain_tx_pool_factory: offchain_transaction_pool_factory.clone(),
+		};
 
-/// Builds a new service for a full client.
-pub fn new_full(config: Configuration)
--> Result<impl AbstractService, ServiceError>
-{
-	new_full!(config).map(|(service, _)| service)
+		// the GRANDPA voter task is considered infallible, i.e.
+		// if it fails we take down the service with it.
+		task_manager.spawn_essential_handle().spawn_blocking(
+			"grandpa-voter",
+			sc_finality_grandpa::run_grandpa_voter(grandpa_params)?,
+		);
+	} else {
+		sc_finality_grandpa::setup_disabled_grandpa(
+			client.clone(),
+			&inherent_data_providers,
+			network.clone(),
+		)?;
+	}
+
+	network_starter.start_network();
+	Ok(task_manager)
 }
 
 /// Builds a new service for a light client.
-pub fn new_light(config: Configuration)
--> Result<impl AbstractService, ServiceError> {
-	let inherent_data_providers = InherentDataProviders::new();
+pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+	configure_wasm_executor(&mut config);
+	let offchain_worker_enabled = config.offchain_worker.enabled;
 
-	let service = ServiceBuilder::new_light::<Block, RuntimeApi, edgeware_executor::Executor>(config)?
-		.with_select_chain(|_config, backend| {
-			Ok(LongestChain::new(backend.clone()))
-		})?
-		.with_transaction_pool(|builder| {
-			let fetcher = builder.fetcher()
-				.ok_or_else(|| "Trying to start light transaction pool without active fetcher")?;
-			let pool_api = sc_transaction_pool::LightChainApi::new(
-				builder.client().clone(),
-				fetcher,
-			);
-			let pool = sc_transaction_pool::BasicPool::with_revalidation_type(
-				builder.config().transaction_pool.clone(),
-				Arc::new(pool_api),
-				builder.prometheus_registry(),
-				sc_transaction_pool::RevalidationType::Light,
-			);
-			Ok(pool)
-		})?
-		.with_import_queue_and_fprb(|
-			_config,
-			client,
-			backend,
-			fetcher,
-			_select_chain,
-			_tx_pool,
-			spawn_task_handle,
-			prometheus_registry,
-		| {
-			let fetch_checker = fetcher
-				.map(|fetcher| fetcher.checker().clone())
-				.ok_or_else(|| "Trying to start light import queue without active fetch checker")?;
-			let grandpa_block_import = sc_finality_grandpa::light_block_import(
-				client.clone(),
-				backend,
-				&(client.clone() as Arc<_>),
-				Arc::new(fetch_checker),
-			)?;
-
-			let finality_proof_import = grandpa_block_import.clone();
-			let finality_proof_request_builder =
-				finality_proof_import.create_finality_proof_request_builder();
-
-			let import_queue = sc_consensus_aura::import_queue::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair, _>(
-				sc_consensus_aura::slot_duration(&*client)?,
-				grandpa_block_import,
-				None,
-				Some(Box::new(finality_proof_import)),
-				client,
-				inherent_data_providers.clone(),
-				spawn_task_handle,
-				prometheus_registry,
-			)?;
-
-			Ok((import_queue, finality_proof_request_builder))
-		})?
-		.with_finality_proof_provider(|client, backend| {
-			// GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
-			let provider = client as Arc<dyn StorageAndProofProvider<_, _>>;
-			Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, provider)) as _)
-		})?
-		.with_rpc_extensions(|builder| {
-			let fetcher = builder.fetcher()
-				.ok_or_else(|| "Trying to start node RPC without active fetcher")?;
-			let remote_blockchain = builder.remote_backend()
-				.ok_or_else(|| "Trying to start node RPC without active remote blockchain")?;
-
-			let light_deps = edgeware_rpc::LightDeps {
-				remote_blockchain,
-				fetcher,
-				client: builder.client().clone(),
-				pool: builder.pool(),
-			};
+	let (client, backend, keystore_container, mut task_manager, on_demand) =
+		sc_service::new_light_parts::<Block, RuntimeApi, edgeware_executor::Executor>(&config)?;
+
+	let select_chain = LongestChain::new(backend.clone());
 
-			Ok(edgeware_rpc::create_light(light_deps))
-		})?
-		.build_light()?;
+	let transaction_pool = Arc::new(sc_transaction_pool::BasicPool::new_light(
+		config.transaction_pool.clone(),
+		config.prometheus_registry(),
+		task_manager.spawn_handle(),
+		on_demand.clone(),
+	));
 
-	Ok(service)
+	let inherent_data_providers = InherentDataProviders::new();
+
+	let grandpa_block_import = sc_finality_grandpa::light_block_import(
+		client.clone(),
+		backend.clone(),
+		&(client.clone() as Arc<_>),
+		Arc::new(on_demand.checker().clone()),
+	)?;
+	let finality_proof_import = grandpa_block_import.clone();
+	let finality_proof_request_builder = finality_proof_import.create_finality_proof_request_builder();
+
+	let import_queue = sc_consensus_aura::import_queue::<_, _, _, sp_consensus_aura::ed25519::AuthorityPair, _>(
+		sc_consensus_aura::slot_duration(&*client)?,
+		grandpa_block_import,
+		None,
+		Some(Box::new(finality_proof_import)),
+		client.clone(),
+		inherent_data_providers.clone(),
+		&task_manager.spawn_handle(),
+		config.prometheus_registry(),
+	)?;
+
+	let (network, system_rpc_tx, network_starter) = sc_service::build_network(sc_service::BuildNetworkParams {
+		config: &config,
+		client: client.clone(),
+		transaction_pool: transaction_pool.clone(),
+		spawn_handle: task_manager.spawn_handle(),
+		import_queue,
+		on_demand: Some(on_demand.clone()),
+		block_announce_validator_builder: Some(Box::new(|_| Box::new(finality_proof_request_builder))),
+		// The light client builds no `grandpa_link`/shared authority set of its own, so
+		// it has nothing to construct a `warp_proof::NetworkProvider` from; it bootstraps
+		// via the finality-proof-request builder wired in above instead.
+		warp_sync: None,
+	})?;
+
+	if offchain_worker_enabled {
+		let offchain_transaction_pool_factory =
+			sc_transaction_pool_api::OffchainTransactionPoolFactory::new(transaction_pool.clone());
+		let offchain_workers = Arc::new(sc_offchain::OffchainWorkers::new(
+			client.clone(),
+			Some(offchain_transaction_pool_factory),
+		));
+
+		task_manager.spawn_handle().spawn(
+			"offchain-workers-runner",
+			client.import_notification_stream().for_each(move |notification| {
+				sc_offchain::OffchainWorkers::on_block_imported(&offchain_workers, &notification.header);
+				futures::future::ready(())
+			}),
+		);
+	}
+
+	let light_deps = edgeware_rpc::LightDeps {
+		remote_blockchain: backend.remote_blockchain(),
+		fetcher: on_demand.clone(),
+		client: client.clone(),
+		pool: transaction_pool.clone(),
+	};
+	sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+		network: network.clone(),
+		client: client.clone(),
+		keystore: keystore_container.sync_keystore(),
+		task_manager: &mut task_manager,
+		transaction_pool,
+		rpc_extensions_builder: Box::new(move |_, _| Ok(edgeware_rpc::create_light(light_deps.clone()))),
+		backend,
+		system_rpc_tx,
+		config,
+	})?;
+
+	network_starter.start_network();
+	Ok(task_manager)
 }